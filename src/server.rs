@@ -3,17 +3,31 @@ use std::collections::HashMap;
 use std::fmt;
 use std::net::SocketAddr;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use askama::Template;
 use axum::extract::{Path, Query, State};
 use axum::handler::Handler;
 use axum::{routing::get, Router};
 use axum_macros::debug_handler;
-use serde::{de, Deserialize, Deserializer};
+use fluent::FluentArgs;
+use serde::{de, Deserialize, Deserializer, Serialize};
 use tower_http::services::ServeDir;
 use tower_http::trace::TraceLayer;
 use tracing::{instrument, Instrument};
 
+mod error;
+mod extract;
+mod guard;
+mod l10n;
+mod negotiate;
+
+use error::AppError;
+use extract::OptionalPath;
+use guard::{guarded, GuardExt, HasQueryParam, HeaderEquals};
+use l10n::{Localizer, RequestLocale};
+use negotiate::{AcceptsJson, Negotiated};
+
 #[derive(Template)] // this will generate the code...
 #[template(path = "index.html")] // using the template in this path, relative to the `templates` dir in the crate root
 struct IndexTemplate<'a> {
@@ -25,7 +39,7 @@ async fn index() -> IndexTemplate<'static> {
     IndexTemplate { name: "world" }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize)]
 struct Language {
     name: &'static str,
     year: u32,
@@ -60,55 +74,115 @@ const LANGUAGES: [Language; 6] = [
 
 // This is an example of a template using template inheritance for consistency
 // It also shows how to use loops in the template
-#[derive(Template)]
+// The view carries the data shared by the HTML and JSON representations.
+// It is both an Askama `Template` (for HTML) and `Serialize` (for JSON), so a
+// `Negotiated<LanguagesView>` can render either depending on the `Accept` header.
+#[derive(Template, Serialize)]
 #[template(path = "languages/index.html")]
-struct LanguagesTemplate {
+struct LanguagesView {
     headline: String,
     languages: Vec<Language>, // the field name should match the variable name in the template
 }
 
-async fn languages() -> LanguagesTemplate {
-    LanguagesTemplate {
-        headline: "Languages".to_string(),
+async fn languages(
+    State(app_state): State<AppState>,
+    RequestLocale(locale): RequestLocale,
+    accepts_json: AcceptsJson,
+) -> Negotiated<LanguagesView> {
+    let headline = app_state
+        .localizer
+        .lookup(&locale, "languages-headline", None);
+    let view = LanguagesView {
+        headline,
         languages: LANGUAGES.into(),
-    }
+    };
+    Negotiated::new(view, accepts_json)
+}
+
+// The API counterpart of `languages`, selected by a route guard when the
+// client sends `X-Api: true`. It always answers with JSON regardless of the
+// `Accept` header.
+async fn languages_api(
+    State(app_state): State<AppState>,
+    RequestLocale(locale): RequestLocale,
+) -> axum::Json<LanguagesView> {
+    let headline = app_state
+        .localizer
+        .lookup(&locale, "languages-headline", None);
+    axum::Json(LanguagesView {
+        headline,
+        languages: LANGUAGES.into(),
+    })
 }
 
 // Use debug_handler to get better error messages in case the handler is not correctly defined
 #[debug_handler]
-// Path is an Axum Extract to get the matched value from the path (see below in the route configuration)
-async fn languages_from_year_path(Path(year): Path<u32>) -> LanguagesTemplate {
+// `OptionalPath` lets this one handler back both `/languages/years` (no
+// segment, lists everything) and `/languages/years/:year` (filters). Both
+// cases funnel through `LanguagesFilter::accepts` so the path, query and
+// struct-query handlers share a single filtering function.
+async fn languages_from_year_path(
+    State(app_state): State<AppState>,
+    RequestLocale(locale): RequestLocale,
+    accepts_json: AcceptsJson,
+    OptionalPath(year): OptionalPath<u32>,
+) -> Negotiated<LanguagesView> {
+    let filter = match year {
+        Some(year) => LanguagesFilter::for_year(year),
+        None => LanguagesFilter::any(),
+    };
     let matches = LANGUAGES
-        .iter()
-        .filter(|l| l.year == year)
-        .map(|l| l.clone())
+        .into_iter()
+        .filter(|l| filter.accepts(l))
         .collect();
-    let headline = format!("Languages from {}", year);
-    LanguagesTemplate {
+    let headline = match year {
+        Some(year) => {
+            let mut args = FluentArgs::new();
+            args.set("year", year);
+            app_state
+                .localizer
+                .lookup(&locale, "languages-from-year", Some(&args))
+        }
+        None => app_state
+            .localizer
+            .lookup(&locale, "languages-headline", None),
+    };
+    let view = LanguagesView {
         headline,
         languages: matches,
-    }
+    };
+    Negotiated::new(view, accepts_json)
 }
 
+// Bad input (`?year=abc` or a missing `year`) now yields a clean `400` via
+// `AppError` instead of panicking the request task. We remember the `Accept`
+// decision so the error can be rendered as HTML or JSON to match the listing.
 async fn languages_from_year_query(
+    State(app_state): State<AppState>,
+    RequestLocale(locale): RequestLocale,
+    accepts_json: AcceptsJson,
     Query(params): Query<HashMap<String, String>>,
-) -> LanguagesTemplate {
-    // No error handling since this fn is a demonstration of Query extraction
+) -> Result<Negotiated<LanguagesView>, AppError> {
     let year = params
         .get("year")
-        .expect("expected query parameter years ")
+        .ok_or_else(|| AppError::missing("year", accepts_json.0))?
         .parse::<u32>()
-        .expect("expected a valid number for year");
+        .map_err(|err| AppError::invalid("year", err.to_string(), accepts_json.0))?;
+    let filter = LanguagesFilter::for_year(year);
     let matches = LANGUAGES
-        .iter()
-        .filter(|l| l.year == year)
-        .map(|l| l.clone())
+        .into_iter()
+        .filter(|l| filter.accepts(l))
         .collect();
-    let headline = format!("Languages from {}", year);
-    LanguagesTemplate {
+    let mut args = FluentArgs::new();
+    args.set("year", year);
+    let headline = app_state
+        .localizer
+        .lookup(&locale, "languages-from-year", Some(&args));
+    let view = LanguagesView {
         headline,
         languages: matches,
-    }
+    };
+    Ok(Negotiated::new(view, accepts_json))
 }
 
 /// Axum can use `serde` to deserialize the query parameters into a struct
@@ -119,6 +193,25 @@ pub(crate) struct LanguagesFilter {
 }
 
 impl LanguagesFilter {
+    /// A filter that accepts every language (used when no year is given).
+    fn any() -> Self {
+        Self {
+            year_from_inclusive: None,
+            year_to_exclusive: None,
+        }
+    }
+
+    /// A filter matching exactly one year, i.e. `[year, year + 1)`.
+    ///
+    /// At `u32::MAX` there is no `year + 1`, so the upper bound is left open
+    /// rather than overflowing — the `from` bound still pins it to that year.
+    fn for_year(year: u32) -> Self {
+        Self {
+            year_from_inclusive: Some(year),
+            year_to_exclusive: year.checked_add(1),
+        }
+    }
+
     /// Check if a language is accepted through the filter
     fn accepts(&self, language: &Language) -> bool {
         let year = language.year;
@@ -129,40 +222,50 @@ impl LanguagesFilter {
 }
 
 /// We can define handlers with a typed struct instead of the raw query parameters
-async fn languages_by_struct_query(filter: Query<LanguagesFilter>) -> LanguagesTemplate {
+async fn languages_by_struct_query(
+    State(app_state): State<AppState>,
+    RequestLocale(locale): RequestLocale,
+    accepts_json: AcceptsJson,
+    filter: Query<LanguagesFilter>,
+) -> Negotiated<LanguagesView> {
     // No error handling since this fn is a demonstration of Query extraction
     let matches = LANGUAGES
         .into_iter()
         .filter(|l| filter.accepts(l))
         .map(|l| l.clone())
         .collect();
-    let headline = match (&filter.year_from_inclusive, &filter.year_to_exclusive) {
+    let mut args = FluentArgs::new();
+    let key = match (&filter.year_from_inclusive, &filter.year_to_exclusive) {
         (Some(from), Some(to)) => {
-            format!(
-                "Languages from year {} (inclusive) to {} (exclusive)",
-                from, to
-            )
+            args.set("from", *from);
+            args.set("to", *to);
+            "languages-range"
         }
         (Some(from), None) => {
-            format!("Languages from year {} and onwards", from)
+            args.set("from", *from);
+            "languages-from"
         }
         (None, Some(to)) => {
-            format!("Languages before year {}", to)
-        }
-        (None, None) => {
-            format!("Languages from any year")
+            args.set("to", *to);
+            "languages-before"
         }
+        (None, None) => "languages-any",
     };
-    LanguagesTemplate {
+    let headline = app_state.localizer.lookup(&locale, key, Some(&args));
+    let view = LanguagesView {
         headline,
         languages: matches,
-    }
+    };
+    Negotiated::new(view, accepts_json)
 }
 
 #[derive(Clone)]
 struct AppState {
     old_languages: Vec<Language>,
     new_languages: Vec<Language>,
+    // The localizer is shared read-only across every request, so we keep it
+    // behind an `Arc` to make cloning the state per request cheap.
+    localizer: Arc<Localizer>,
 }
 
 impl AppState {
@@ -172,6 +275,7 @@ impl AppState {
         Self {
             old_languages,
             new_languages,
+            localizer: Arc::new(Localizer::new()),
         }
     }
 }
@@ -180,15 +284,15 @@ impl AppState {
 // Note the `#[debug_handler]` macro - it makes the compiler errors more readable
 // in case the handler is not correctly defined.
 #[debug_handler]
-async fn stateful_old_languages(State(app_state): State<AppState>) -> LanguagesTemplate {
-    LanguagesTemplate {
+async fn stateful_old_languages(State(app_state): State<AppState>) -> LanguagesView {
+    LanguagesView {
         headline: "Old Languages".to_string(),
         languages: app_state.old_languages.clone(),
     }
 }
 
-async fn stateful_new_languages(State(app_state): State<AppState>) -> LanguagesTemplate {
-    LanguagesTemplate {
+async fn stateful_new_languages(State(app_state): State<AppState>) -> LanguagesView {
+    LanguagesView {
         headline: "New Languages".to_string(),
         languages: app_state.new_languages.clone(),
     }
@@ -206,6 +310,9 @@ pub(crate) fn router<T>() -> Router<T>
 where
     T: Clone + Send + Sync + 'static,
 {
+    // Build the state up front so the guarded `/languages/` service can bake
+    // it in (a `route_service` must be state-less by the time it is mounted).
+    let state = AppState::new();
     Router::new()
         // Route the root to the index fn above
         .route("/", get(index))
@@ -216,9 +323,28 @@ where
         // .route_service would route on the root path to the service only
         .nest_service("/assets", ServeDir::new("assets"))
         // Route the /languages path to the languages fn above
-        // This is an example of a using templates with inheritance
-        .route("/languages/", get(languages))
-        // We can capture a part of the path as a parameter and pass it to the handler
+        // This is an example of a using templates with inheritance.
+        // A route guard serves the HTML listing normally but dispatches to the
+        // JSON API handler when the request asks for it, either via an
+        // `X-Api: true` header or a `?api` query parameter.
+        .route_service(
+            "/languages/",
+            guarded()
+                .when(
+                    HeaderEquals::new("x-api", "true").or(HasQueryParam::new("api")),
+                    get(languages_api),
+                )
+                .otherwise(get(languages))
+                .service(state.clone()),
+        )
+        // The same listing under a locale prefix so the `RequestLocale`
+        // extractor can pick the language up from the `:lang` path segment
+        // (e.g. `/da/languages/`) instead of the `Accept-Language` header.
+        .route("/:lang/languages/", get(languages))
+        // A single handler backs both the bare listing and the filtered one
+        // via the `OptionalPath` extractor: `/languages/years` lists
+        // everything, `/languages/years/1960` filters to that year.
+        .route("/languages/years", get(languages_from_year_path))
         .route("/languages/years/:year", get(languages_from_year_path))
         // We can also capture the query parameters and get the year from the query string:
         .route("/languages/year", get(languages_from_year_query))
@@ -227,7 +353,7 @@ where
         // We can have state in the application and pass it to the handlers
         // This changes the signature of the Router and the handler functions
         .nest("/stateful/", stateful_router())
-        .with_state(AppState::new())
+        .with_state(state)
         // Add tracing to the router (i.e. trace all of the above)
         .layer(TraceLayer::new_for_http())
 }
@@ -0,0 +1,152 @@
+//! Application error type.
+//!
+//! [`AppError`] replaces the `.expect(...)` panics in the query handlers with a
+//! typed error that implements [`IntoResponse`]. It models bad input as a `400`
+//! and renders the body either as an Askama error page or as a JSON problem
+//! document depending on the request's `Accept` header (captured when the error
+//! is built). Template render failures are not modelled here: a handler returns
+//! its `Template`/`Negotiated` directly and Askama's own `IntoResponse` turns a
+//! render error into a `500`.
+use askama::Template;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+
+/// The ways a request can fail in a handler.
+///
+/// Each variant carries the negotiation decision (`json`) so [`IntoResponse`]
+/// can pick the representation without the request, which it no longer has
+/// access to at that point.
+pub(crate) enum AppError {
+    /// A required parameter was not supplied (e.g. a missing `?year=`).
+    MissingParameter { name: String, json: bool },
+    /// A parameter was supplied but could not be parsed.
+    InvalidParameter {
+        name: String,
+        reason: String,
+        json: bool,
+    },
+}
+
+impl AppError {
+    /// A missing-parameter error, remembering whether the client wants JSON.
+    pub(crate) fn missing(name: impl Into<String>, json: bool) -> Self {
+        AppError::MissingParameter {
+            name: name.into(),
+            json,
+        }
+    }
+
+    /// An invalid-parameter error, remembering whether the client wants JSON.
+    pub(crate) fn invalid(name: impl Into<String>, reason: impl Into<String>, json: bool) -> Self {
+        AppError::InvalidParameter {
+            name: name.into(),
+            reason: reason.into(),
+            json,
+        }
+    }
+
+    /// The HTTP status this error maps to: `400` for bad input.
+    fn status(&self) -> StatusCode {
+        match self {
+            AppError::MissingParameter { .. } | AppError::InvalidParameter { .. } => {
+                StatusCode::BAD_REQUEST
+            }
+        }
+    }
+
+    /// A human-readable message describing the error.
+    fn message(&self) -> String {
+        match self {
+            AppError::MissingParameter { name, .. } => {
+                format!("missing required parameter `{}`", name)
+            }
+            AppError::InvalidParameter { name, reason, .. } => {
+                format!("invalid value for parameter `{}`: {}", name, reason)
+            }
+        }
+    }
+
+    /// Whether the client asked for a JSON body.
+    fn wants_json(&self) -> bool {
+        match self {
+            AppError::MissingParameter { json, .. }
+            | AppError::InvalidParameter { json, .. } => *json,
+        }
+    }
+}
+
+/// The JSON body returned for an error when the client accepts JSON.
+#[derive(Serialize)]
+struct ProblemBody {
+    status: u16,
+    message: String,
+}
+
+/// The HTML error page, mirroring the other templates in this crate.
+#[derive(Template)]
+#[template(path = "error.html")]
+struct ErrorTemplate {
+    status: u16,
+    message: String,
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let message = self.message();
+        if self.wants_json() {
+            let body = ProblemBody {
+                status: status.as_u16(),
+                message,
+            };
+            (status, Json(body)).into_response()
+        } else {
+            let page = ErrorTemplate {
+                status: status.as_u16(),
+                message,
+            };
+            (status, page).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::http::header::CONTENT_TYPE;
+
+    #[test]
+    fn bad_input_maps_to_400() {
+        assert_eq!(
+            AppError::missing("year", false).into_response().status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            AppError::invalid("year", "bad", false)
+                .into_response()
+                .status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[test]
+    fn negotiates_json_or_html_body() {
+        let json = AppError::missing("year", true).into_response();
+        assert_eq!(
+            json.headers().get(CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+
+        let html = AppError::missing("year", false).into_response();
+        let content_type = html
+            .headers()
+            .get(CONTENT_TYPE)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert!(content_type.starts_with("text/html"));
+    }
+}
@@ -0,0 +1,95 @@
+//! Custom extractors.
+//!
+//! [`OptionalPath`] lets a single handler back both a route with a trailing
+//! dynamic segment and one without it, mirroring how an optional-path
+//! extractor lets one handler serve both `/blog` and `/blog/:page`.
+use std::fmt;
+use std::str::FromStr;
+
+use axum::extract::{FromRequestParts, RawPathParams};
+use axum::http::request::Parts;
+use axum::http::StatusCode;
+
+/// Extracts a dynamic path segment that may be absent.
+///
+/// Returns `None` when the route matched without the dynamic segment (e.g.
+/// `/languages/years`) and `Some(T)` when the segment is present and parses
+/// (e.g. `/languages/years/1960`). A present-but-unparseable segment is a
+/// `400 Bad Request` rather than a silent `None`.
+pub(crate) struct OptionalPath<T>(pub(crate) Option<T>);
+
+impl<T, S> FromRequestParts<S> for OptionalPath<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // `RawPathParams` rejects when the route captured no parameters or a
+        // segment is not valid UTF-8; pass its status and message through.
+        let params = RawPathParams::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| (rejection.status(), rejection.body_text()))?;
+
+        // The consolidated routes capture at most one dynamic segment, so we
+        // key off the last captured parameter.
+        match params.iter().last() {
+            None => Ok(OptionalPath(None)),
+            Some((_, value)) => value
+                .parse::<T>()
+                .map(|parsed| OptionalPath(Some(parsed)))
+                .map_err(|err| (StatusCode::BAD_REQUEST, format!("invalid path segment: {}", err))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::body::Body;
+    use axum::http::Request;
+    use axum::routing::get;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    // The handler encodes the extracted value in the status code so the tests
+    // can distinguish `None` from `Some` without reading the body.
+    async fn handler(OptionalPath(year): OptionalPath<u32>) -> StatusCode {
+        match year {
+            Some(_) => StatusCode::OK,
+            None => StatusCode::NO_CONTENT,
+        }
+    }
+
+    fn app() -> Router {
+        Router::new()
+            .route("/years", get(handler))
+            .route("/years/:year", get(handler))
+    }
+
+    async fn status_of(uri: &str) -> StatusCode {
+        app()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn absent_segment_extracts_none() {
+        assert_eq!(status_of("/years").await, StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn present_parseable_segment_extracts_some() {
+        assert_eq!(status_of("/years/1960").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn present_unparseable_segment_is_bad_request() {
+        assert_eq!(status_of("/years/nineteen").await, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,252 @@
+//! Route-level request guards.
+//!
+//! A [`Guard`] inspects the request [`Parts`] and decides whether a candidate
+//! route should handle the request. [`guarded`] wraps a set of
+//! `(guard, MethodRouter)` candidates into a single service so the same path
+//! can dispatch to different handlers based on request properties: the first
+//! guard that matches wins, and a `404` is returned only when none match.
+//!
+//! ```ignore
+//! .route_service(
+//!     "/languages/",
+//!     guarded()
+//!         .when(HeaderEquals::new("x-api", "true"), get(languages_api))
+//!         .otherwise(get(languages))
+//!         .service(state.clone()),
+//! )
+//! ```
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use axum::body::Body;
+use axum::http::request::Parts;
+use axum::http::{HeaderName, Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::MethodRouter;
+use tower::Service;
+
+/// A predicate over a request, used to pick between candidate routes.
+pub(crate) trait Guard: Send + Sync + 'static {
+    /// Whether this guard accepts the request described by `parts`.
+    fn matches(&self, parts: &Parts) -> bool;
+}
+
+/// Matches when a header equals a given value (case-insensitive on the value,
+/// mirroring how header tokens are usually compared).
+pub(crate) struct HeaderEquals {
+    name: HeaderName,
+    value: String,
+}
+
+impl HeaderEquals {
+    pub(crate) fn new(name: &'static str, value: impl Into<String>) -> Self {
+        Self {
+            name: HeaderName::from_static(name),
+            value: value.into(),
+        }
+    }
+}
+
+impl Guard for HeaderEquals {
+    fn matches(&self, parts: &Parts) -> bool {
+        parts
+            .headers
+            .get(&self.name)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.eq_ignore_ascii_case(&self.value))
+            .unwrap_or(false)
+    }
+}
+
+/// Matches when the query string contains a parameter with the given name,
+/// regardless of its value.
+pub(crate) struct HasQueryParam(pub(crate) String);
+
+impl HasQueryParam {
+    pub(crate) fn new(name: impl Into<String>) -> Self {
+        Self(name.into())
+    }
+}
+
+impl Guard for HasQueryParam {
+    fn matches(&self, parts: &Parts) -> bool {
+        parts
+            .uri
+            .query()
+            .into_iter()
+            .flat_map(|query| query.split('&'))
+            .map(|pair| pair.split('=').next().unwrap_or(pair))
+            .any(|key| key == self.0)
+    }
+}
+
+/// Matches only when both inner guards match.
+// Part of the combinator toolkit; `Or` is used by `router()` while `And` is
+// offered for callers that need conjunction.
+#[allow(dead_code)]
+pub(crate) struct And<A, B>(A, B);
+
+impl<A: Guard, B: Guard> Guard for And<A, B> {
+    fn matches(&self, parts: &Parts) -> bool {
+        self.0.matches(parts) && self.1.matches(parts)
+    }
+}
+
+/// Matches when either inner guard matches.
+pub(crate) struct Or<A, B>(A, B);
+
+impl<A: Guard, B: Guard> Guard for Or<A, B> {
+    fn matches(&self, parts: &Parts) -> bool {
+        self.0.matches(parts) || self.1.matches(parts)
+    }
+}
+
+/// Always matches; used internally as the `otherwise` fallthrough.
+struct Always;
+
+impl Guard for Always {
+    fn matches(&self, _parts: &Parts) -> bool {
+        true
+    }
+}
+
+/// Combinators for building compound guards fluently.
+pub(crate) trait GuardExt: Guard + Sized {
+    /// Require both `self` and `other` to match.
+    #[allow(dead_code)] // companion to `or`; part of the public combinator API
+    fn and<G: Guard>(self, other: G) -> And<Self, G> {
+        And(self, other)
+    }
+
+    /// Require either `self` or `other` to match.
+    fn or<G: Guard>(self, other: G) -> Or<Self, G> {
+        Or(self, other)
+    }
+}
+
+impl<T: Guard> GuardExt for T {}
+
+/// Builder collecting guarded route candidates in priority order.
+pub(crate) struct Guarded<S> {
+    candidates: Vec<(Arc<dyn Guard>, MethodRouter<S>)>,
+}
+
+/// Start building a guarded route.
+pub(crate) fn guarded<S>() -> Guarded<S> {
+    Guarded {
+        candidates: Vec::new(),
+    }
+}
+
+impl<S> Guarded<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    /// Add a candidate that handles the request when `guard` matches.
+    pub(crate) fn when<G: Guard>(mut self, guard: G, router: MethodRouter<S>) -> Self {
+        self.candidates.push((Arc::new(guard), router));
+        self
+    }
+
+    /// Add a final candidate that handles the request when nothing else did.
+    pub(crate) fn otherwise(self, router: MethodRouter<S>) -> Self {
+        self.when(Always, router)
+    }
+
+    /// Finish the builder into a service, baking in the application state so
+    /// the resulting service is ready to mount with `route_service`.
+    pub(crate) fn service(self, state: S) -> GuardService {
+        let candidates = self
+            .candidates
+            .into_iter()
+            .map(|(guard, router)| (guard, router.with_state(state.clone())))
+            .collect();
+        GuardService {
+            candidates: Arc::new(candidates),
+        }
+    }
+}
+
+/// The service produced by [`Guarded::service`].
+///
+/// On each request it walks the candidates in order and dispatches to the
+/// first whose guard matches, falling back to the next one otherwise. When no
+/// guard matches it returns `404`; a matched candidate is free to return `405`
+/// itself if the method does not fit.
+#[derive(Clone)]
+pub(crate) struct GuardService {
+    candidates: Arc<Vec<(Arc<dyn Guard>, MethodRouter<()>)>>,
+}
+
+impl Service<Request<Body>> for GuardService {
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let candidates = self.candidates.clone();
+        let (parts, body) = req.into_parts();
+        Box::pin(async move {
+            for (guard, router) in candidates.iter() {
+                if guard.matches(&parts) {
+                    let mut router = router.clone();
+                    let req = Request::from_parts(parts, body);
+                    return Ok(router.call(req).await.into_response());
+                }
+            }
+            Ok(StatusCode::NOT_FOUND.into_response())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build the request `Parts` for a bare GET to `uri`, optionally with one
+    /// header set, so the guards can be exercised in isolation.
+    fn parts(uri: &str, header: Option<(&'static str, &str)>) -> Parts {
+        let mut builder = Request::builder().uri(uri);
+        if let Some((name, value)) = header {
+            builder = builder.header(name, value);
+        }
+        let (parts, _) = builder.body(Body::empty()).unwrap().into_parts();
+        parts
+    }
+
+    #[test]
+    fn header_equals_matches_case_insensitively() {
+        let guard = HeaderEquals::new("x-api", "true");
+        assert!(guard.matches(&parts("/", Some(("x-api", "TRUE")))));
+        assert!(!guard.matches(&parts("/", Some(("x-api", "false")))));
+        assert!(!guard.matches(&parts("/", None)));
+    }
+
+    #[test]
+    fn has_query_param_ignores_the_value() {
+        let guard = HasQueryParam::new("year");
+        assert!(guard.matches(&parts("/?year=1960", None)));
+        assert!(guard.matches(&parts("/?year=", None)));
+        assert!(!guard.matches(&parts("/?other=1", None)));
+        assert!(!guard.matches(&parts("/", None)));
+    }
+
+    #[test]
+    fn and_or_combinators_compose() {
+        let both = HeaderEquals::new("x-api", "true").and(HasQueryParam::new("year"));
+        assert!(both.matches(&parts("/?year=1960", Some(("x-api", "true")))));
+        assert!(!both.matches(&parts("/", Some(("x-api", "true")))));
+
+        let either = HeaderEquals::new("x-api", "true").or(HasQueryParam::new("year"));
+        assert!(either.matches(&parts("/?year=1960", None)));
+        assert!(either.matches(&parts("/", Some(("x-api", "true")))));
+        assert!(!either.matches(&parts("/", None)));
+    }
+}
@@ -0,0 +1,182 @@
+//! Localization (l10n) support for the templates.
+//!
+//! This module holds a [`Localizer`] that loads Fluent message bundles
+//! (`.ftl` files) per locale at startup and resolves translated strings for
+//! the handlers. Handlers pick up the request locale through the
+//! [`RequestLocale`] extractor and then build their template strings via
+//! [`Localizer::lookup`] instead of the hard-coded English `format!` calls we
+//! started out with.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::extract::{FromRequestParts, Path};
+use axum::http::header::ACCEPT_LANGUAGE;
+use axum::http::request::Parts;
+use fluent::{FluentArgs, FluentBundle, FluentResource};
+use unic_langid::LanguageIdentifier;
+
+/// The locale used when the request does not ask for anything we recognize.
+/// We keep it as a plain `&str` so it can be parsed into a
+/// [`LanguageIdentifier`] wherever it is needed.
+pub(crate) const DEFAULT_LOCALE: &str = "en";
+
+/// The message bundles we ship with, as `(locale, source)` pairs.
+///
+/// We embed them with `include_str!` so the binary is self-contained and the
+/// `Localizer` can be built without touching the filesystem at run time. Add a
+/// new locale by dropping another `.ftl` file in `locales/` and listing it
+/// here.
+const BUNDLES: &[(&str, &str)] = &[
+    ("en", include_str!("../../locales/en/main.ftl")),
+    ("da", include_str!("../../locales/da/main.ftl")),
+];
+
+/// Holds the loaded Fluent bundles, keyed by locale.
+///
+/// The `Localizer` is built once at startup and kept behind an `Arc` in the
+/// application state (see [`crate::server::AppState`]) so cloning it per
+/// request is cheap.
+pub(crate) struct Localizer {
+    bundles: HashMap<LanguageIdentifier, FluentBundle<FluentResource>>,
+    default: LanguageIdentifier,
+}
+
+impl Localizer {
+    /// Build a `Localizer` from the embedded [`BUNDLES`].
+    ///
+    /// This is done eagerly at startup so a malformed `.ftl` file fails fast
+    /// rather than on the first request that needs it.
+    pub(crate) fn new() -> Self {
+        let mut bundles = HashMap::new();
+        for (locale, source) in BUNDLES {
+            let langid: LanguageIdentifier = locale
+                .parse()
+                .expect("bundled locale tag should be a valid language identifier");
+            let resource = FluentResource::try_new(source.to_string())
+                .expect("bundled .ftl resource should parse");
+            let mut bundle = FluentBundle::new(vec![langid.clone()]);
+            bundle
+                .add_resource(resource)
+                .expect("bundled .ftl resource should not collide");
+            bundles.insert(langid, bundle);
+        }
+        let default = DEFAULT_LOCALE
+            .parse()
+            .expect("default locale should be a valid language identifier");
+        Self { bundles, default }
+    }
+
+    /// Resolve a message for `locale`, falling back to the default locale when
+    /// the requested locale (or the message key within it) is unknown.
+    ///
+    /// Unknown keys never panic: a missing message in the requested locale is
+    /// retried against the default locale, and if that also misses we return
+    /// the key itself so the page still renders.
+    pub(crate) fn lookup(
+        &self,
+        locale: &LanguageIdentifier,
+        key: &str,
+        args: Option<&FluentArgs>,
+    ) -> String {
+        self.lookup_in(locale, key, args)
+            .or_else(|| self.lookup_in(&self.default, key, args))
+            .unwrap_or_else(|| key.to_string())
+    }
+
+    /// Try to resolve `key` in a single bundle, returning `None` if either the
+    /// locale or the message is absent.
+    fn lookup_in(
+        &self,
+        locale: &LanguageIdentifier,
+        key: &str,
+        args: Option<&FluentArgs>,
+    ) -> Option<String> {
+        let bundle = self.bundles.get(locale)?;
+        let message = bundle.get_message(key)?;
+        let pattern = message.value()?;
+        let mut errors = Vec::new();
+        let value = bundle.format_pattern(pattern, args, &mut errors);
+        Some(value.into_owned())
+    }
+}
+
+/// Extractor resolving the locale for a request.
+///
+/// The locale is taken, in order of preference, from the `:lang` path segment
+/// (so `/:lang/languages/` works), then from the `Accept-Language` header, and
+/// finally from [`DEFAULT_LOCALE`]. We only parse the tag here; whether we have
+/// a bundle for it is decided later by [`Localizer::lookup`].
+pub(crate) struct RequestLocale(pub(crate) LanguageIdentifier);
+
+impl<S> FromRequestParts<S> for RequestLocale
+where
+    S: Send + Sync,
+{
+    // Extracting a locale can never fail: we always fall back to the default.
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let from_path = Path::<HashMap<String, String>>::from_request_parts(parts, state)
+            .await
+            .ok()
+            .and_then(|Path(params)| params.get("lang").and_then(|lang| lang.parse().ok()));
+
+        let locale = from_path
+            .or_else(|| locale_from_accept_language(parts))
+            .unwrap_or_else(|| {
+                DEFAULT_LOCALE
+                    .parse()
+                    .expect("default locale should be a valid language identifier")
+            });
+
+        Ok(RequestLocale(locale))
+    }
+}
+
+/// Parse the first valid language tag out of the `Accept-Language` header, in
+/// the order the header lists them. Quality values are ignored, so a later tag
+/// with a higher `q` does not win over an earlier one.
+fn locale_from_accept_language(parts: &Parts) -> Option<LanguageIdentifier> {
+    let header = parts.headers.get(ACCEPT_LANGUAGE)?.to_str().ok()?;
+    header
+        .split(',')
+        .map(|tag| tag.split(';').next().unwrap_or(tag).trim())
+        .find_map(|tag| tag.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locale(tag: &str) -> LanguageIdentifier {
+        tag.parse().unwrap()
+    }
+
+    #[test]
+    fn looks_up_a_known_key_in_the_requested_locale() {
+        let localizer = Localizer::new();
+        assert_eq!(
+            localizer.lookup(&locale("da"), "languages-headline", None),
+            "Programmeringssprog"
+        );
+    }
+
+    #[test]
+    fn unknown_locale_falls_back_to_the_default_locale() {
+        let localizer = Localizer::new();
+        // `fr` is not bundled, so we expect the English (default) message.
+        assert_eq!(
+            localizer.lookup(&locale("fr"), "languages-headline", None),
+            "Languages"
+        );
+    }
+
+    #[test]
+    fn unknown_key_falls_back_to_the_key_itself() {
+        let localizer = Localizer::new();
+        assert_eq!(
+            localizer.lookup(&locale("en"), "no-such-key", None),
+            "no-such-key"
+        );
+    }
+}
@@ -0,0 +1,75 @@
+//! Content negotiation between HTML templates and JSON.
+//!
+//! The language handlers return a [`Negotiated<T>`] responder that inspects
+//! the request's `Accept` header and renders either the Askama HTML template
+//! or a JSON body, so a single route serves both browsers and API clients.
+use axum::extract::FromRequestParts;
+use axum::http::header::ACCEPT;
+use axum::http::request::Parts;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+
+/// The JSON media type we negotiate against.
+const APPLICATION_JSON: &str = "application/json";
+
+/// Small extractor capturing whether the client's `Accept` header lists JSON.
+/// Quality values are not weighed: any mention of `application/json` counts,
+/// even when another media type has a higher `q`.
+///
+/// We only need the yes/no answer here, so we collapse the `Accept` header to
+/// a boolean rather than carrying the raw header around.
+#[derive(Clone, Copy)]
+pub(crate) struct AcceptsJson(pub(crate) bool);
+
+impl<S> FromRequestParts<S> for AcceptsJson
+where
+    S: Send + Sync,
+{
+    // Reading the `Accept` header never fails: a missing or opaque header just
+    // means "not JSON", i.e. render HTML.
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let accepts_json = parts
+            .headers
+            .get(ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.contains(APPLICATION_JSON))
+            .unwrap_or(false);
+        Ok(AcceptsJson(accepts_json))
+    }
+}
+
+/// A responder that renders `T` either as Askama HTML or as JSON.
+///
+/// `T` must be both an Askama [`Template`](askama::Template) (for the HTML
+/// branch) and [`Serialize`] (for the JSON branch). The chosen representation
+/// is decided by the captured [`AcceptsJson`] flag at response time.
+pub(crate) struct Negotiated<T> {
+    data: T,
+    accepts_json: bool,
+}
+
+impl<T> Negotiated<T> {
+    /// Wrap `data` together with the negotiation decision taken from the
+    /// request's `Accept` header.
+    pub(crate) fn new(data: T, AcceptsJson(accepts_json): AcceptsJson) -> Self {
+        Self { data, accepts_json }
+    }
+}
+
+impl<T> IntoResponse for Negotiated<T>
+where
+    T: askama::Template + Serialize,
+{
+    fn into_response(self) -> Response {
+        if self.accepts_json {
+            Json(&self.data).into_response()
+        } else {
+            // Defer to Askama's own `IntoResponse`, which sets the HTML
+            // content type and turns a render error into a 500.
+            self.data.into_response()
+        }
+    }
+}